@@ -6,7 +6,10 @@ use std::{
     convert::TryFrom,
     mem::{align_of, size_of},
     ops::{Deref, DerefMut},
-    sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst},
+    sync::atomic::{
+        AtomicUsize,
+        Ordering::{Acquire, Relaxed, Release, SeqCst},
+    },
 };
 
 use crossbeam_epoch::{pin, Atomic, Guard, Owned, Shared};
@@ -15,12 +18,24 @@ use crate::{debug_delay, pagecache::Page};
 
 #[allow(unused)]
 #[doc(hidden)]
-pub const PAGETABLE_NODE_SZ: usize = size_of::<Node1<()>>();
+pub const PAGETABLE_NODE_SZ: usize = size_of::<Node>();
 
 const FAN_FACTOR: usize = 18;
 const FAN_OUT: usize = 1 << FAN_FACTOR;
 const FAN_MASK: usize = FAN_OUT - 1;
 
+// Number of radix levels needed to address the full `PageId` bit width,
+// peeling `FAN_FACTOR` bits per level. Derived from `PageId` so the whole
+// keyspace is reachable without a hand-tuned ceiling. The lowest level is the
+// packed tip of pages; the `NODE_LEVELS - 1` levels above it are interior
+// nodes.
+const NODE_LEVELS: usize =
+    (PageId::BITS as usize + FAN_FACTOR - 1) / FAN_FACTOR;
+
+// Depth of the deepest interior node, whose children are `Tip`s rather than
+// further `Node`s.
+const TIP_PARENT_LEVEL: usize = NODE_LEVELS - 2;
+
 pub type PageId = u64;
 
 pub struct PageView<'g> {
@@ -31,31 +46,44 @@ pub struct PageView<'g> {
 impl<'g> PageView<'g> {
     fn rcu<'b, F, B>(
         &self,
-        f: F,
+        mut f: F,
         guard: &'b Guard,
     ) -> Result<B, Shared<'b, Page>>
     where
         F: FnMut(&mut Page) -> B,
     {
-        let mut old_pointer = self.read;
+        // the revision we are replacing; updated each time an in-place update
+        // races us so the next CAS compares against what is actually linked.
+        let mut current = self.read;
         loop {
-            let mut clone: Owned<Page> = Owned::new(self.deref().clone());
+            let mut clone: Owned<Page> =
+                Owned::new(unsafe { current.deref().clone() });
             let b = f(clone.deref_mut());
 
             let result =
-                self.entry.compare_and_set(self.read, clone, SeqCst, guard);
+                self.entry.compare_and_set(current, clone, SeqCst, guard);
 
             match result {
-                Ok(_) => return Ok(b),
+                Ok(_) => {
+                    // the previous revision has been unlinked; readers pinned
+                    // on an older epoch may still hold it, so reclaim it once
+                    // all of those epochs advance.
+                    unsafe {
+                        guard.defer_destroy(current);
+                    }
+                    return Ok(b);
+                }
                 Err(cas_error)
                     if cas_error.current.version() == self.version() =>
                 {
-                    // we got here because the page was moved to a new
-                    // location.
-                    old_pointer = cas_error.current;
+                    // the page was updated in place; retry against the
+                    // revision that is installed now.
+                    current = cas_error.current;
                     continue;
                 }
                 Err(cas_error) => {
+                    // the page moved to a new location; the caller must
+                    // re-traverse.
                     return Err(cas_error.current);
                 }
             }
@@ -79,98 +107,136 @@ impl<'g> Deref for PageView<'g> {
     }
 }
 
-struct Node1 {
-    children: [Atomic<Node2>; FAN_OUT],
+// An interior radix node, linking to `FAN_OUT` children one level down. The
+// deepest interior node (`TIP_PARENT_LEVEL`) links to `Tip`s instead; since
+// `Atomic<Node>` and `Atomic<Tip>` are both a single pointer-sized word, that
+// node reinterprets its `children` array in place via `tip_slots`.
+struct Node {
+    children: [Atomic<Node>; FAN_OUT],
+    // number of non-null children linked below; lets `compact` decide whether
+    // this node can be detached, and lets the walks prune empty subtrees,
+    // without an O(FAN_OUT) scan.
+    population: AtomicUsize,
 }
 
-struct Node2 {
+// The packed tip level: `FAN_OUT` consecutive pages addressed by the final
+// `FAN_FACTOR` bits of a `PageId`, so a dense run of ids shares one array
+// rather than allocating a node apiece.
+struct Tip {
     children: [Atomic<Page>; FAN_OUT],
+    // number of non-null pages installed; same role as `Node::population`.
+    population: AtomicUsize,
 }
 
-impl Node1 {
+impl Node {
     fn new() -> Owned<Self> {
-        let size = size_of::<Self>();
-        let align = align_of::<Self>();
+        unsafe { Owned::from_raw(alloc_node::<Self>()) }
+    }
 
+    // Reinterpret this node's children as `Tip` pointers. Only valid at
+    // `TIP_PARENT_LEVEL`, where the slots actually hold `Tip`s.
+    fn tip_slots(&self) -> &[Atomic<Tip>; FAN_OUT] {
+        #[allow(clippy::cast_ptr_alignment)]
         unsafe {
-            let layout = Layout::from_size_align_unchecked(size, align);
-
-            #[allow(clippy::cast_ptr_alignment)]
-            let ptr = alloc_zeroed(layout) as *mut Self;
-
-            Owned::from_raw(ptr)
+            &*(&self.children as *const [Atomic<Node>; FAN_OUT]
+                as *const [Atomic<Tip>; FAN_OUT])
         }
     }
 }
 
-impl Node2 {
-    fn new() -> Owned<Node2> {
-        let size = size_of::<Self>();
-        let align = align_of::<Self>();
-
-        unsafe {
-            let layout = Layout::from_size_align_unchecked(size, align);
-
-            #[allow(clippy::cast_ptr_alignment)]
-            let ptr = alloc_zeroed(layout) as *mut Self;
-
-            Owned::from_raw(ptr)
-        }
+impl Tip {
+    fn new() -> Owned<Self> {
+        unsafe { Owned::from_raw(alloc_node::<Self>()) }
     }
 }
 
-impl Drop for Node1 {
-    fn drop(&mut self) {
-        drop_iter(self.children.iter());
-    }
+// The located tip slot for a `PageId`, carrying enough context to detect that
+// a concurrent `compact` detached the tip between `traverse` returning and the
+// caller committing its write.
+struct TipRef<'g> {
+    // deepest interior node, which links `tip` at `parent_idx`.
+    parent: &'g Node,
+    parent_idx: usize,
+    // the tip pointer as it was linked when traversed.
+    tip_ptr: Shared<'g, Tip>,
+    tip: &'g Tip,
+    // page slot within `tip`.
+    idx: usize,
 }
 
-impl Drop for Node2 {
-    fn drop(&mut self) {
-        drop_iter(self.children.iter());
+impl<'g> TipRef<'g> {
+    // The page slot this ref addresses.
+    fn slot(&self) -> &'g Atomic<Page> {
+        &self.tip.children[self.idx]
     }
-}
 
-fn drop_iter<T>(iter: core::slice::Iter<'_, Atomic<T>>) {
-    let guard = pin();
-    for child in iter {
-        let shared_child = child.load(Relaxed, &guard);
-        if shared_child.is_null() {
-            // this does not leak because the PageTable is
-            // assumed to be dense.
-            break;
-        }
-        unsafe {
-            drop(shared_child.into_owned());
-        }
+    // Whether `tip` is still linked from its parent, i.e. a concurrent
+    // `compact` has not detached it since `traverse` observed it.
+    fn still_linked(&self, guard: &Guard) -> bool {
+        self.parent.tip_slots()[self.parent_idx].load(Acquire, guard)
+            == self.tip_ptr
     }
 }
 
+// Shared zeroed allocation for the node structs, which are too large to place
+// on the stack before boxing.
+#[allow(clippy::cast_ptr_alignment)]
+unsafe fn alloc_node<T>() -> *mut T {
+    let layout =
+        Layout::from_size_align_unchecked(size_of::<T>(), align_of::<T>());
+
+    alloc_zeroed(layout) as *mut T
+}
+
 /// A simple lock-free radix tree.
 pub struct PageTable {
-    head: Atomic<Node1>,
+    head: Atomic<Node>,
 }
 
 impl Default for PageTable {
     fn default() -> Self {
-        let head = Node1::new();
+        let head = Node::new();
         Self { head: Atomic::from(head) }
     }
 }
 
 impl PageTable {
-    /// # Panics
-    ///
-    /// will panic if the item is not null already,
-    /// which represents a serious failure to
-    /// properly handle lifecycles of pages in the
-    /// using system.
+    /// Install `item` at `pid`. If a revision was already present it is
+    /// unlinked and scheduled for epoch-deferred destruction, so `insert`
+    /// doubles as a leak-free page-version update.
     pub fn insert(&self, pid: PageId, item: Page, guard: &Guard) {
-        debug_delay();
-        let tip = self.traverse(pid, guard);
+        let mut item = Owned::new(item);
+        loop {
+            debug_delay();
+            let tref = self.traverse(pid, guard);
+
+            let old = tref.slot().swap(item, Release, guard);
 
-        let old = tip.swap(Owned::new(item), Release, guard);
-        assert!(old.is_null());
+            debug_delay();
+            if tref.still_linked(guard) {
+                // committed into a tip that is still reachable from the root.
+                if old.is_null() {
+                    // first non-null install in this tip slot.
+                    tref.tip.population.fetch_add(1, Release);
+                } else {
+                    // overwriting an existing revision; defer its destruction
+                    // so any reader still pinned on an older epoch can finish
+                    // with it.
+                    unsafe {
+                        guard.defer_destroy(old);
+                    }
+                }
+                return;
+            }
+
+            // a concurrent `compact` detached the tip between `traverse` and
+            // the swap. Pull our page back out of the now-unreachable tip and
+            // retry the whole traversal. A detached tip was empty, so `old` is
+            // null and restoring it leaves the tip safe to reclaim.
+            item = unsafe {
+                tref.slot().swap(old, Release, guard).into_owned()
+            };
+        }
     }
 
     /// Try to get a value from the tree.
@@ -180,43 +246,376 @@ impl PageTable {
         guard: &'g Guard,
     ) -> Option<PageView<'g>> {
         debug_delay();
-        let tip = self.traverse(pid, guard);
+        let tref = self.traverse(pid, guard);
+        let entry = tref.slot();
 
-        let res = tip.load(Acquire, guard);
+        let res = entry.load(Acquire, guard);
         if res.is_null() {
             None
         } else {
-            let page_view = PageView { read: res, entry: tip };
+            let page_view = PageView { read: res, entry };
 
             Some(page_view)
         }
     }
 
-    fn traverse<'g>(self, k: PageId, guard: &'g Guard) -> &'g Atomic<Page> {
-        let (l1k, l2k) = split_fanout(k);
+    /// Remove a `PageId` from the tree, scheduling its `Page` for
+    /// epoch-deferred destruction. Leaves the interior nodes in place;
+    /// call [`compact`](PageTable::compact) to reclaim nodes that have been
+    /// fully emptied.
+    ///
+    /// Returns `true` if a live page was unlinked, `false` if the slot was
+    /// already empty.
+    pub fn remove(&self, pid: PageId, guard: &Guard) -> bool {
+        loop {
+            debug_delay();
+            let tref = self.traverse(pid, guard);
+            let entry = tref.slot();
+
+            debug_delay();
+            let old = entry.load(Acquire, guard);
+            if old.is_null() {
+                return false;
+            }
+
+            debug_delay();
+            match entry.compare_and_set(old, Shared::null(), Release, guard) {
+                Ok(_) => {
+                    tref.tip.population.fetch_sub(1, Release);
+                    unsafe {
+                        guard.defer_destroy(old);
+                    }
+                    return true;
+                }
+                Err(_) => {
+                    // a concurrent `rcu`/`insert` replaced `old` in this slot;
+                    // retry against the revision installed now rather than
+                    // reporting a no-op that left a live page behind.
+                    continue;
+                }
+            }
+        }
+    }
+
+    /// Iterate over every live `(PageId, PageView)` currently installed, in
+    /// ascending `PageId` order. Recovery, GC, and snapshot logic use this to
+    /// sweep the whole table rather than probing known ids with `get`.
+    pub fn iter<'g>(
+        &self,
+        guard: &'g Guard,
+    ) -> impl Iterator<Item = (PageId, PageView<'g>)> {
+        let mut out = Vec::new();
+        let head = unsafe { self.head.load(Acquire, guard).deref() };
+        self.collect(head, 0, 0, guard, &mut out);
+        out.into_iter()
+    }
 
+    /// The number of live pages installed. Walks the tree summing the tip
+    /// population counters rather than materializing every entry, and prunes
+    /// subtrees that hold nothing.
+    pub fn len(&self, guard: &Guard) -> usize {
         debug_delay();
-        let head = self.head.load(Acquire, guard);
+        let head = unsafe { self.head.load(Acquire, guard).deref() };
+        self.count(head, 0, guard)
+    }
 
+    /// Whether the table holds no live pages. Returns as soon as the first
+    /// live tip is found instead of counting them all.
+    pub fn is_empty(&self, guard: &Guard) -> bool {
         debug_delay();
-        let l1 = unsafe { head.deref().children };
+        let head = unsafe { self.head.load(Acquire, guard).deref() };
+        !self.any_live(head, 0, guard)
+    }
+
+    fn count(&self, node: &Node, level: usize, guard: &Guard) -> usize {
+        if node.population.load(Acquire) == 0 {
+            return 0;
+        }
+
+        let mut live = 0;
+        if level == TIP_PARENT_LEVEL {
+            let slots = node.tip_slots();
+            for idx in 0..FAN_OUT {
+                let tip = slots[idx].load(Acquire, guard);
+                if tip.is_null() {
+                    continue;
+                }
+                live += unsafe { tip.deref() }.population.load(Acquire);
+            }
+            return live;
+        }
+
+        for idx in 0..FAN_OUT {
+            let child = node.children[idx].load(Acquire, guard);
+            if child.is_null() {
+                continue;
+            }
+            live += self.count(unsafe { child.deref() }, level + 1, guard);
+        }
+        live
+    }
+
+    fn any_live(&self, node: &Node, level: usize, guard: &Guard) -> bool {
+        if node.population.load(Acquire) == 0 {
+            return false;
+        }
+
+        if level == TIP_PARENT_LEVEL {
+            let slots = node.tip_slots();
+            for idx in 0..FAN_OUT {
+                let tip = slots[idx].load(Acquire, guard);
+                if tip.is_null() {
+                    continue;
+                }
+                if unsafe { tip.deref() }.population.load(Acquire) != 0 {
+                    return true;
+                }
+            }
+            return false;
+        }
+
+        for idx in 0..FAN_OUT {
+            let child = node.children[idx].load(Acquire, guard);
+            if child.is_null() {
+                continue;
+            }
+            if self.any_live(unsafe { child.deref() }, level + 1, guard) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn collect<'g>(
+        &self,
+        node: &'g Node,
+        level: usize,
+        prefix: PageId,
+        guard: &'g Guard,
+        out: &mut Vec<(PageId, PageView<'g>)>,
+    ) {
+        if level == TIP_PARENT_LEVEL {
+            let slots = node.tip_slots();
+            for idx in 0..FAN_OUT {
+                debug_delay();
+                let tip = slots[idx].load(Acquire, guard);
+                if tip.is_null() {
+                    // `compact` can detach an emptied tip, leaving a hole;
+                    // skip it so later live ids are not truncated away.
+                    continue;
+                }
+                let tip = unsafe { tip.deref() };
+                let prefix = (prefix << FAN_FACTOR) | PageId::try_from(idx).unwrap();
+                for tidx in 0..FAN_OUT {
+                    let read = tip.children[tidx].load(Acquire, guard);
+                    if read.is_null() {
+                        continue;
+                    }
+                    let pid = (prefix << FAN_FACTOR)
+                        | PageId::try_from(tidx).unwrap();
+                    out.push((
+                        pid,
+                        PageView { read, entry: &tip.children[tidx] },
+                    ));
+                }
+            }
+            return;
+        }
+
+        for idx in 0..FAN_OUT {
+            debug_delay();
+            let child = node.children[idx].load(Acquire, guard);
+            if child.is_null() {
+                // `compact` can detach an emptied subtree at any interior
+                // level, so skip holes rather than stopping at the first one.
+                continue;
+            }
+
+            let child = unsafe { child.deref() };
+            let prefix = (prefix << FAN_FACTOR) | PageId::try_from(idx).unwrap();
+            self.collect(child, level + 1, prefix, guard, out);
+        }
+    }
+
+    /// Reclaim interior nodes and tips that no longer hold any live pages,
+    /// returning their zeroed `FAN_OUT`-wide arrays to the allocator.
+    /// Only tips whose population counter reads zero have their page slots
+    /// scanned, and interior nodes are detached on their counter alone, so a
+    /// populated tip costs one counter load rather than an O(FAN_OUT) scan.
+    ///
+    /// Detachment is safe against a concurrent `traverse`: once a parent slot
+    /// is CASed back to null, a racing inserter that loaded the stale child
+    /// pointer re-checks the slot and re-traverses (see `traverse`).
+    pub fn compact(&self, guard: &Guard) {
+        debug_delay();
+        let head = unsafe { self.head.load(Acquire, guard).deref() };
+        // the root is never detached, only its subtrees.
+        self.compact_children(head, 0, guard);
+    }
+
+    // Recursively reclaim the empty children of the interior node at `level`.
+    fn compact_children(&self, node: &Node, level: usize, guard: &Guard) {
+        if node.population.load(Acquire) == 0 {
+            return;
+        }
+
+        if level == TIP_PARENT_LEVEL {
+            let slots = node.tip_slots();
+            for idx in 0..FAN_OUT {
+                debug_delay();
+                let tip_ptr = slots[idx].load(Acquire, guard);
+                if tip_ptr.is_null() {
+                    continue;
+                }
+
+                let tip = unsafe { tip_ptr.deref() };
+                if !tip_is_empty(tip, guard) {
+                    continue;
+                }
+
+                debug_delay();
+                if slots[idx]
+                    .compare_and_set(tip_ptr, Shared::null(), Release, guard)
+                    .is_ok()
+                {
+                    node.population.fetch_sub(1, Release);
+                    unsafe {
+                        guard.defer_destroy(tip_ptr);
+                    }
+                }
+            }
+            return;
+        }
+
+        for idx in 0..FAN_OUT {
+            debug_delay();
+            let child_ptr = node.children[idx].load(Acquire, guard);
+            if child_ptr.is_null() {
+                // holes can appear once subtrees are detached, so skip rather
+                // than stop at the first null slot.
+                continue;
+            }
+
+            let child = unsafe { child_ptr.deref() };
+            self.compact_children(child, level + 1, guard);
+            if child.population.load(Acquire) != 0 {
+                continue;
+            }
+
+            debug_delay();
+            if node.children[idx]
+                .compare_and_set(child_ptr, Shared::null(), Release, guard)
+                .is_ok()
+            {
+                node.population.fetch_sub(1, Release);
+                unsafe {
+                    guard.defer_destroy(child_ptr);
+                }
+            }
+        }
+    }
+
+    fn traverse<'g>(&self, k: PageId, guard: &'g Guard) -> TipRef<'g> {
+        let indices = fanout(k);
+        let idx = indices[NODE_LEVELS - 1];
+        let parent_idx = indices[TIP_PARENT_LEVEL];
+
+        'retry: loop {
+            debug_delay();
+            let mut node = unsafe { self.head.load(Acquire, guard).deref() };
+
+            // descend the interior node levels above the deepest one.
+            for &i in &indices[..TIP_PARENT_LEVEL] {
+                match self.descend_node(node, i, guard) {
+                    Some(child) => node = child,
+                    None => continue 'retry,
+                }
+            }
 
+            // the deepest interior node links to the tip level.
+            match self.descend_tip(node, parent_idx, guard) {
+                Some((tip_ptr, tip)) => {
+                    return TipRef { parent: node, parent_idx, tip_ptr, tip, idx }
+                }
+                None => continue 'retry,
+            }
+        }
+    }
+
+    // Load, or lazily CAS-install, the child `Node` at `idx`. Returns `None`
+    // if a concurrent `compact` detached it, signalling the caller to retry.
+    fn descend_node<'g>(
+        &self,
+        node: &'g Node,
+        idx: usize,
+        guard: &'g Guard,
+    ) -> Option<&'g Node> {
         debug_delay();
-        let mut l2_ptr = l1[l1k].load(Acquire, guard);
+        let mut child = node.children[idx].load(Acquire, guard);
 
-        if l2_ptr.is_null() {
-            let next_child = Node2::new();
+        if child.is_null() {
+            let next_child = Node::new();
 
             debug_delay();
-            let ret = l1[l1k].compare_and_set(
+            let ret = node.children[idx].compare_and_set(
                 Shared::null(),
                 next_child,
                 Release,
                 guard,
             );
 
-            l2_ptr = match ret {
-                Ok(next_child) => next_child,
+            child = match ret {
+                Ok(installed) => {
+                    node.population.fetch_add(1, Release);
+                    installed
+                }
+                Err(returned) => {
+                    drop(returned.new);
+                    returned.current
+                }
+            };
+        }
+
+        debug_delay();
+        // a concurrent `compact` may have detached this child by CASing the
+        // slot back to null. Re-check that the slot still points at the node
+        // we loaded before descending into it; if it moved, start over.
+        if node.children[idx].load(Acquire, guard) != child {
+            return None;
+        }
+
+        Some(unsafe { child.deref() })
+    }
+
+    // Load, or lazily CAS-install, the `Tip` at `idx` below the deepest
+    // interior node. Returns `None` on concurrent detachment, as above.
+    fn descend_tip<'g>(
+        &self,
+        node: &'g Node,
+        idx: usize,
+        guard: &'g Guard,
+    ) -> Option<(Shared<'g, Tip>, &'g Tip)> {
+        let slots = node.tip_slots();
+
+        debug_delay();
+        let mut tip = slots[idx].load(Acquire, guard);
+
+        if tip.is_null() {
+            let next_tip = Tip::new();
+
+            debug_delay();
+            let ret = slots[idx].compare_and_set(
+                Shared::null(),
+                next_tip,
+                Release,
+                guard,
+            );
+
+            tip = match ret {
+                Ok(installed) => {
+                    node.population.fetch_add(1, Release);
+                    installed
+                }
                 Err(returned) => {
                     drop(returned.new);
                     returned.current
@@ -225,28 +624,34 @@ impl PageTable {
         }
 
         debug_delay();
-        let l2 = unsafe { l2_ptr.deref().children };
+        if slots[idx].load(Acquire, guard) != tip {
+            return None;
+        }
 
-        &l2[l2k]
+        Some((tip, unsafe { tip.deref() }))
     }
 }
 
+// Whether every page slot in `tip` is null, confirming the population counter
+// before the tip is detached so a racing install is never dropped.
+fn tip_is_empty(tip: &Tip, guard: &Guard) -> bool {
+    if tip.population.load(Acquire) != 0 {
+        return false;
+    }
+    tip.children.iter().all(|c| c.load(Acquire, guard).is_null())
+}
+
+/// Decompose a `PageId` into its per-level radix indices, most-significant
+/// level first. `FAN_FACTOR` bits are peeled per level across `NODE_LEVELS`
+/// levels, which spans the full `PageId` bit width.
 #[inline]
-fn split_fanout(id: PageId) -> (usize, usize) {
-    // right shift 32 on 32-bit pointer systems panics
-    #[cfg(target_pointer_width = "64")]
-    assert!(
-        id <= 1 << (FAN_FACTOR * 2),
-        "trying to access key of {}, which is \
-         higher than 2 ^ {}",
-        id,
-        (FAN_FACTOR * 2)
-    );
-
-    let left = id >> FAN_FACTOR;
-    let right = id & u64::try_from(FAN_MASK).unwrap();
-
-    (safe_usize(left), safe_usize(right))
+fn fanout(id: PageId) -> [usize; NODE_LEVELS] {
+    let mut indices = [0; NODE_LEVELS];
+    for (level, slot) in indices.iter_mut().enumerate() {
+        let shift = FAN_FACTOR * (NODE_LEVELS - 1 - level);
+        *slot = safe_usize((id >> shift) & PageId::try_from(FAN_MASK).unwrap());
+    }
+    indices
 }
 
 #[inline]
@@ -258,20 +663,188 @@ impl Drop for PageTable {
     fn drop(&mut self) {
         let guard = pin();
         let head = self.head.load(Relaxed, &guard);
-        unsafe {
-            drop(head.into_owned());
+        if !head.is_null() {
+            unsafe {
+                free_node(head, 0, &guard);
+            }
         }
     }
 }
 
+// Recursively free an interior node and everything below it. Holes left by
+// `remove`/`compact` are skipped, so a gap never truncates the walk.
+unsafe fn free_node(ptr: Shared<'_, Node>, level: usize, guard: &Guard) {
+    let node = ptr.deref();
+    if level == TIP_PARENT_LEVEL {
+        for slot in node.tip_slots().iter() {
+            let tip = slot.load(Relaxed, guard);
+            if !tip.is_null() {
+                free_tip(tip, guard);
+            }
+        }
+    } else {
+        for slot in node.children.iter() {
+            let child = slot.load(Relaxed, guard);
+            if !child.is_null() {
+                free_node(child, level + 1, guard);
+            }
+        }
+    }
+    drop(ptr.into_owned());
+}
+
+unsafe fn free_tip(ptr: Shared<'_, Tip>, guard: &Guard) {
+    let tip = ptr.deref();
+    for slot in tip.children.iter() {
+        let page = slot.load(Relaxed, guard);
+        if !page.is_null() {
+            drop(page.into_owned());
+        }
+    }
+    drop(ptr.into_owned());
+}
+
+#[test]
+fn test_fanout() {
+    // low ids live entirely in the least-significant level.
+    let mut expected = [0; NODE_LEVELS];
+    expected[NODE_LEVELS - 1] = 0b11_1111_1111_1111_1111;
+    assert_eq!(fanout(0b11_1111_1111_1111_1111), expected);
+
+    // one bit past a level boundary spills into the next level up.
+    let mut expected = [0; NODE_LEVELS];
+    expected[NODE_LEVELS - 2] = 0b1;
+    expected[NODE_LEVELS - 1] = 0b11_1111_1111_1111_1111;
+    assert_eq!(fanout(0b111_1111_1111_1111_1111), expected);
+
+    // the very top of the u64 range is addressable without panicking.
+    let top_shift = FAN_FACTOR * (NODE_LEVELS - 1);
+    assert_eq!(fanout(PageId::MAX)[0], safe_usize(PageId::MAX >> top_shift));
+}
+
+#[cfg(test)]
+fn test_page() -> Page {
+    Page::default()
+}
+
+#[test]
+fn test_insert_get() {
+    let pt = PageTable::default();
+    let guard = pin();
+
+    assert!(pt.get(0, &guard).is_none());
+
+    pt.insert(0, test_page(), &guard);
+    pt.insert(1, test_page(), &guard);
+
+    assert!(pt.get(0, &guard).is_some());
+    assert!(pt.get(1, &guard).is_some());
+    assert!(pt.get(2, &guard).is_none());
+}
+
 #[test]
-fn test_split_fanout() {
-    assert_eq!(
-        split_fanout(0b11_1111_1111_1111_1111),
-        (0, 0b11_1111_1111_1111_1111)
-    );
-    assert_eq!(
-        split_fanout(0b111_1111_1111_1111_1111),
-        (0b1, 0b11_1111_1111_1111_1111)
-    );
+fn test_remove_reports_work() {
+    let pt = PageTable::default();
+    let guard = pin();
+
+    pt.insert(7, test_page(), &guard);
+    assert!(pt.remove(7, &guard));
+    // a second removal is a no-op and must say so.
+    assert!(!pt.remove(7, &guard));
+    assert!(pt.get(7, &guard).is_none());
+}
+
+#[test]
+fn test_remove_then_compact_keeps_neighbors() {
+    let pt = PageTable::default();
+    let guard = pin();
+
+    // `lonely` sits alone in its tip; the others live in neighbouring tips
+    // and subtrees so compaction of `lonely`'s tip must not disturb them.
+    let lonely = 0;
+    let same_parent = PageId::try_from(FAN_OUT).unwrap();
+    let other_subtree = 1 << (FAN_FACTOR * (NODE_LEVELS - 1));
+
+    for pid in &[lonely, same_parent, other_subtree] {
+        pt.insert(*pid, test_page(), &guard);
+    }
+
+    assert!(pt.remove(lonely, &guard));
+    pt.compact(&guard);
+
+    assert!(pt.get(lonely, &guard).is_none());
+    assert!(pt.get(same_parent, &guard).is_some());
+    assert!(pt.get(other_subtree, &guard).is_some());
+}
+
+#[test]
+fn test_len_is_empty() {
+    let pt = PageTable::default();
+    let guard = pin();
+
+    assert!(pt.is_empty(&guard));
+    assert_eq!(pt.len(&guard), 0);
+
+    pt.insert(0, test_page(), &guard);
+    pt.insert(FAN_OUT as PageId, test_page(), &guard);
+    assert!(!pt.is_empty(&guard));
+    assert_eq!(pt.len(&guard), 2);
+
+    assert!(pt.remove(0, &guard));
+    assert_eq!(pt.len(&guard), 1);
+    assert!(!pt.is_empty(&guard));
+
+    assert!(pt.remove(FAN_OUT as PageId, &guard));
+    assert!(pt.is_empty(&guard));
+    assert_eq!(pt.len(&guard), 0);
+}
+
+#[test]
+fn test_iter_skips_compacted_holes() {
+    let pt = PageTable::default();
+    let guard = pin();
+
+    // compacting `hole` detaches its tip, leaving a hole *below* surviving
+    // higher ids in the same deepest parent and in another top-level subtree.
+    let hole = 0;
+    let same_parent = PageId::try_from(FAN_OUT).unwrap();
+    let other_subtree = 1 << (FAN_FACTOR * (NODE_LEVELS - 1));
+
+    for pid in &[hole, same_parent, other_subtree] {
+        pt.insert(*pid, test_page(), &guard);
+    }
+
+    assert!(pt.remove(hole, &guard));
+    pt.compact(&guard);
+
+    let mut seen: Vec<PageId> =
+        pt.iter(&guard).map(|(pid, _view)| pid).collect();
+    seen.sort_unstable();
+
+    // the removed id is gone, but iteration does not truncate at its hole.
+    assert_eq!(seen, vec![same_parent, other_subtree]);
+    assert_eq!(pt.len(&guard), 2);
+}
+
+#[test]
+fn test_high_sparse_pageid_roundtrip() {
+    // folding the per-level indices back together must reproduce the id for
+    // sparse, high PageIds across the full u64 range.
+    let reconstruct = |indices: [usize; NODE_LEVELS]| -> PageId {
+        indices.iter().fold(0, |acc, &i| {
+            (acc << FAN_FACTOR) | PageId::try_from(i).unwrap()
+        })
+    };
+
+    for &pid in &[
+        0,
+        1,
+        PageId::try_from(FAN_OUT).unwrap(),
+        0xDEAD_BEEF_1234_5678,
+        1 << 40,
+        1 << 60,
+        PageId::MAX,
+    ] {
+        assert_eq!(reconstruct(fanout(pid)), pid);
+    }
 }